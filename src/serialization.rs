@@ -1,41 +1,81 @@
 // Implements fmt::Display for the given type, outputing a kind of 'ID' string
 // built deterministically from the type's serde serialization.
 //
+// The encoding is injective: every reserved character that could otherwise be
+// confused with one of the structural separators below (`{`, `}`, `[`, `]`,
+// `,`, the colon-as-underscore marker, the escape character itself, quotes,
+// backslashes, spaces, and raw control characters) is escaped as `~XX`, where
+// `XX` is the two-digit uppercase hex of the original byte. This guarantees
+// that two different values never collapse onto the same ID, and that the ID
+// can be parsed back with `impl_deterministic_parse_from_serde!` (except for
+// a value holding a `NaN`/`Inf`/`-Inf` float; see `decode_deterministic_id`).
+//
+// The encoding is also canonical with respect to key order: every
+// `HashMap`/`serde_json::Map`'s entries are sorted bytewise by key before
+// the ID is emitted, so the same logical value always produces the same ID
+// regardless of that map's iteration order (which, depending on whether the
+// `preserve_order` feature is enabled upstream, is otherwise either
+// insertion order or arbitrary per-process order). Struct and struct-variant
+// fields are left in serde's own (already-deterministic) declaration order
+// and are not re-sorted.
+//
+// A string whose content would otherwise be misread as `null`, `true`,
+// `false`, or a number (e.g. `ProductCode("42")`) is wrapped in `"..."` so
+// it survives the round trip as a string rather than colliding with the
+// real scalar.
+//
+// Numbers are formatted by our own serializer rather than `serde_json`'s:
+// integers keep their exact decimal form, and floats always render as the
+// shortest decimal that round-trips back to the same value, with `-0.0`
+// normalized to `0` and a stable spelling for `NaN`/`Inf`/`-Inf` (values
+// `serde_json` refuses to serialize at all, which would otherwise turn a
+// non-finite field into a panic-inducing `fmt::Error`).
+//
+// Also implements `DeterministicId` for the type, whose fallible `to_id()`
+// is what `Display` is actually built on top of: `Display` can't return
+// anything but `fmt::Error` on failure, so it logs the real `IdError` to
+// stderr and discards it, while `to_id()` lets callers handle the error
+// themselves.
+//
+// An optional second argument selects the encoding grammar via an
+// `IdProfile` (see `IdProfile`, `InjectiveProfile`, `SlugProfile`,
+// `FsSafeProfile`); omitting it is equivalent to passing `InjectiveProfile`.
+// Note that this is *not* the grammar used before the injective-encoding
+// rewrite above: that rewrite changed the default substitution scheme itself
+// (to make it reversible), so any ID already persisted under the old lossy
+// substitution will not match an ID produced today for the same value.
+//
+// By default, a unit enum variant renders as a bare string, so it collides
+// with a plain string field holding the same text (and the reverse is true
+// for `decode_deterministic_id`, which can't tell them apart either). A
+// profile can opt into `IdProfile::disambiguate_unit_variants` to instead
+// tag a unit variant like a null-payload variant (`Variant_null`), trading
+// that legacy compactness for collision-freedom on types that mix unit
+// variants with string data.
+//
 // Some examples of what these IDs look like:
 // - ProductCode("example") -> "example"
 // - SimpleEnum::VariantA -> "VariantA"
-// - SimpleEnum::VariantB("with spaces") -> "VariantB_with-spaces"
+// - SimpleEnum::VariantB("with spaces") -> "VariantB_with~20spaces"
 // - ComplexEnum::VariantB { id: 42, name: "test".to_string() } -> "VariantB_{id_42,name_test}"
+// - ProductCode("42") -> "\"42\"" (quoted so it doesn't decode back as the number 42)
 #[macro_export]
 macro_rules! impl_deterministic_display_from_serde {
     ($type:ty) => {
+        $crate::impl_deterministic_display_from_serde!($type, $crate::serialization::InjectiveProfile);
+    };
+    ($type:ty, $profile:ty) => {
+        impl $crate::serialization::DeterministicId for $type {
+            fn to_id(&self) -> Result<String, $crate::serialization::IdError> {
+                $crate::serialization::encode_deterministic_id_with_profile::<Self, $profile>(self)
+                    .map_err(Into::into)
+            }
+        }
+
         impl std::fmt::Display for $type {
             fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-                serde_json::to_string(self)
-                    // If string, remove surrounding quotes.
-                    .map(|s| {
-                        if s.starts_with('"') && s.ends_with('"') {
-                            s[1..s.len() - 1].to_string()
-                        } else {
-                            s
-                        }
-                    })
-                    // If map, remove surrounding braces.
-                    .map(|s| {
-                        if s.starts_with('{') && s.ends_with('}') {
-                            s[1..s.len() - 1].to_string()
-                        } else {
-                            s
-                        }
-                    })
-                    // Replace special characters.
-                    .map(|s| {
-                        s.replace('\\', "~")
-                            .replace('"', "")
-                            .replace(':', "_")
-                            .replace(' ', "-")
-                            .to_string()
-                    })
+                use $crate::serialization::DeterministicId;
+                self.to_id()
                     .map_err(|e| {
                         eprintln!(
                             "UNHANDLED SERIALIZATION ERROR\n{}.to_string() failed.\n{:?}",
@@ -50,16 +90,1066 @@ macro_rules! impl_deterministic_display_from_serde {
     };
 }
 
+// Implements std::str::FromStr (plus an inherent `from_id`) for the given
+// type, reversing the transform performed by
+// `impl_deterministic_display_from_serde!`. Since the ID format strips one
+// layer of structural wrapping (the outer quotes of a bare string, or the
+// outer braces of an object) without recording which one it was, parsing
+// tries each possible reconstruction in turn and keeps the first one that
+// both matches the grammar and deserializes into `$type`.
+#[macro_export]
+macro_rules! impl_deterministic_parse_from_serde {
+    ($type:ty) => {
+        impl std::str::FromStr for $type {
+            type Err = $crate::serialization::IdParseError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                Self::from_id(s)
+            }
+        }
+
+        impl $type {
+            pub fn from_id(s: &str) -> Result<Self, $crate::serialization::IdParseError> {
+                $crate::serialization::decode_deterministic_id(s)
+            }
+        }
+    };
+}
+
+/// The byte [`InjectiveProfile`] uses to introduce an escape sequence in
+/// deterministic IDs.
+const ESCAPE_BYTE: u8 = b'~';
+
+/// Bytes that are structurally meaningful to the ID grammar itself (the
+/// object/array delimiters, the field separator, the colon-as-underscore
+/// marker, quotes and backslashes left over from JSON, and the literal space
+/// the legacy encoding used to substitute). These are escaped under every
+/// [`IdProfile`], regardless of its own `reserved_bytes()`, since the parser
+/// relies on them never appearing unescaped in content.
+const CORE_RESERVED_BYTES: &[u8] = b" :,{}[]\"\\_";
+
+/// Escapes `s` so that none of its bytes can be confused with a structural
+/// separator of the ID grammar, using [`InjectiveProfile`]'s escape byte and
+/// reserved set. Every reserved byte and every control byte below `0x20` is
+/// replaced by `~XX`, the uppercase hex of the original byte; everything
+/// else (including multi-byte UTF-8 sequences, which never contain a byte in
+/// the reserved set) passes through unchanged.
+pub fn escape_reserved(s: &str) -> String {
+    escape_reserved_with::<InjectiveProfile>(s)
+}
+
+/// Like [`escape_reserved`], but escaping under an arbitrary [`IdProfile`].
+fn escape_reserved_with<P: IdProfile>(s: &str) -> String {
+    let escape_byte = P::escape_byte();
+    let mut out = Vec::with_capacity(s.len());
+    for b in s.bytes() {
+        if b == escape_byte
+            || CORE_RESERVED_BYTES.contains(&b)
+            || P::reserved_bytes().contains(&b)
+            || b < 0x20
+        {
+            out.extend_from_slice(format!("{}{:02X}", escape_byte as char, b).as_bytes());
+        } else {
+            out.push(b);
+        }
+    }
+    // Safe: every byte we didn't escape came straight from a valid `&str`,
+    // and every byte we emitted ourselves is ASCII.
+    String::from_utf8(out).expect("escape_reserved_with always produces valid UTF-8")
+}
+
+/// Reverses [`escape_reserved`]. Only defined for [`InjectiveProfile`]'s
+/// escape byte, since `impl_deterministic_parse_from_serde!` doesn't (yet)
+/// take a profile argument.
+pub fn unescape_reserved(s: &str) -> Result<String, IdParseError> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == ESCAPE_BYTE {
+            let hex = bytes
+                .get(i + 1..i + 3)
+                .and_then(|b| std::str::from_utf8(b).ok())
+                .ok_or_else(|| IdParseError::MalformedEscape(s.to_string()))?;
+            let byte = u8::from_str_radix(hex, 16)
+                .map_err(|_| IdParseError::MalformedEscape(s.to_string()))?;
+            out.push(byte);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).map_err(|_| IdParseError::MalformedEscape(s.to_string()))
+}
+
+/// Configures the grammar a type's deterministic ID is rendered in: which
+/// bytes get escaped beyond the structural core, how enum variants carrying
+/// a payload are tagged, and whether a final case transform is applied.
+/// Passed as the second argument to `impl_deterministic_display_from_serde!`;
+/// omitting it is equivalent to passing [`InjectiveProfile`].
+pub trait IdProfile {
+    /// The byte that introduces an escape sequence. Must not itself need
+    /// escaping under this same profile (i.e. it should be outside of
+    /// `reserved_bytes()` and the fixed grammar delimiters `{}[],_`).
+    fn escape_byte() -> u8 {
+        b'~'
+    }
+
+    /// Bytes (beyond the ID grammar's own fixed delimiters, which are always
+    /// escaped) that this profile additionally escapes in content — e.g. the
+    /// characters a URL or filesystem path component can't contain.
+    fn reserved_bytes() -> &'static [u8] {
+        &[]
+    }
+
+    /// How an enum variant that carries a payload is rendered relative to
+    /// its tag. Defaults to [`VariantTagging::Suffixed`], the legacy
+    /// behavior.
+    fn variant_tagging() -> VariantTagging {
+        VariantTagging::Suffixed
+    }
+
+    /// A final transform applied to the whole rendered ID. Defaults to the
+    /// identity (no transform).
+    fn transform_case(id: String) -> String {
+        id
+    }
+
+    /// Whether a unit enum variant is tagged like a zero-payload variant
+    /// (`Variant_null`) rather than rendered as a bare string (`Variant`).
+    ///
+    /// Defaults to `false`, the legacy behavior, under which a unit variant
+    /// named `"Active"` and a plain string field holding `"Active"` render to
+    /// the same ID. Opt in when a type's payload can mix unit variants with
+    /// string data and the two must never collide.
+    fn disambiguate_unit_variants() -> bool {
+        false
+    }
+}
+
+/// How a struct or tuple enum variant's payload is rendered relative to its
+/// variant tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VariantTagging {
+    /// `Variant_{...}` / `Variant_[...]` — the legacy behavior. The
+    /// outermost object wrapper is stripped regardless of whether it came
+    /// from an enum tag or an ordinary struct/map.
+    Suffixed,
+    /// `{Variant_{...}}` — the externally-tagged JSON shape kept verbatim,
+    /// outer braces included.
+    Wrapped,
+}
+
+/// The encoding grammar `impl_deterministic_display_from_serde!($type)` (no
+/// profile argument) uses: `~` escapes, the core reserved set, suffixed enum
+/// tagging, no case transform. This is the only profile that predates
+/// `IdProfile` itself, so introducing `SlugProfile`/`FsSafeProfile` etc. does
+/// not change its output.
+///
+/// This is *not* byte-compatible with IDs produced before
+/// the injective-encoding rewrite (see the module-level docs above) — that
+/// rewrite changed the substitution scheme this profile implements in order
+/// to make it reversible, which is itself a breaking change for any ID
+/// persisted under the old lossy substitution.
+pub struct InjectiveProfile;
+
+impl IdProfile for InjectiveProfile {}
+
+/// Produces a lowercase, URL-path-safe ID: escapes with `-XX` instead of
+/// `~XX` (so the output never contains `~`), additionally reserves the
+/// characters that are unsafe or meaningful in a URL path segment, and
+/// lowercases the result.
+pub struct SlugProfile;
+
+impl IdProfile for SlugProfile {
+    fn escape_byte() -> u8 {
+        b'-'
+    }
+
+    fn reserved_bytes() -> &'static [u8] {
+        b"/?#&=%+~"
+    }
+
+    fn transform_case(id: String) -> String {
+        id.to_lowercase()
+    }
+}
+
+/// Produces an ID safe to use as a single file or directory name on both
+/// POSIX and Windows filesystems: escapes with `$XX` and additionally
+/// reserves the characters those filesystems forbid in a path component.
+pub struct FsSafeProfile;
+
+impl IdProfile for FsSafeProfile {
+    fn escape_byte() -> u8 {
+        b'$'
+    }
+
+    fn reserved_bytes() -> &'static [u8] {
+        b"/*?<>|"
+    }
+}
+
+/// A tree shape mirroring `serde_json::Value`, except numbers keep their
+/// original integer/float distinction and floats are never rejected — unlike
+/// `serde_json`, which has no way to represent `NaN`/`±Inf` at all, we need
+/// to encode them, not just store them. Built directly by [`IdValueSerializer`]
+/// without ever routing through `serde_json`, so a non-finite float can't
+/// abort the encoding.
+#[derive(Debug, Clone, PartialEq)]
+enum IdValue {
+    Null,
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    Str(String),
+    Array(Vec<IdValue>),
+    /// A struct, struct variant, or enum-tag wrapper. Field order here comes
+    /// straight from serde (declaration order for a struct, insertion order
+    /// for the single synthetic entry a variant wrapper adds), which
+    /// is already deterministic, so [`canonicalize_value`] leaves it alone.
+    Object(Vec<(String, IdValue)>),
+    /// A `HashMap`/`serde_json::Map`-derived object, whose entry order is
+    /// *not* deterministic (it's insertion order, or arbitrary per-process
+    /// order under `preserve_order`). [`canonicalize_value`] sorts these.
+    Map(Vec<(String, IdValue)>),
+}
+
+/// The error produced when a value can't be turned into an [`IdValue`] (in
+/// practice, only a map with a non-string/non-integer key).
+#[derive(Debug)]
+pub struct IdSerializeError(String);
+
+impl std::fmt::Display for IdSerializeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for IdSerializeError {}
+
+impl serde::ser::Error for IdSerializeError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        IdSerializeError(msg.to_string())
+    }
+}
+
+/// Types that can be turned into a deterministic ID string. Implemented by
+/// `impl_deterministic_display_from_serde!`, which also builds `Display` on
+/// top of it — use `to_id()` directly when a serialization failure needs to
+/// be handled rather than just logged.
+pub trait DeterministicId {
+    fn to_id(&self) -> Result<String, IdError>;
+}
+
+/// The error returned by [`DeterministicId::to_id`]: the value couldn't be
+/// turned into an [`IdValue`], either because serialization itself failed
+/// or because it had a shape the encoder doesn't support (currently, only a
+/// map with a non-string/non-integer key — see [`IdSerializeError`]).
+#[derive(Debug)]
+pub enum IdError {
+    Serialize(IdSerializeError),
+}
+
+impl std::fmt::Display for IdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            IdError::Serialize(e) => write!(f, "failed to serialize value into an id: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for IdError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            IdError::Serialize(e) => Some(e),
+        }
+    }
+}
+
+impl From<IdSerializeError> for IdError {
+    fn from(e: IdSerializeError) -> Self {
+        IdError::Serialize(e)
+    }
+}
+
+/// Serializes any `T: Serialize` directly into an [`IdValue`] tree, bypassing
+/// `serde_json::Value` so that non-finite floats survive instead of aborting
+/// serialization. Carries `P` so that shape decisions made mid-serialization
+/// — currently, only whether a unit variant is tagged (see
+/// [`IdProfile::disambiguate_unit_variants`]) — can depend on the profile.
+struct IdValueSerializer<P>(std::marker::PhantomData<P>);
+
+impl<P> IdValueSerializer<P> {
+    fn new() -> Self {
+        IdValueSerializer(std::marker::PhantomData)
+    }
+}
+
+macro_rules! forward_to_i64 {
+    ($($method:ident: $ty:ty),* $(,)?) => {
+        $(
+            fn $method(self, v: $ty) -> Result<IdValue, IdSerializeError> {
+                self.serialize_i64(v as i64)
+            }
+        )*
+    };
+}
+
+macro_rules! forward_to_u64 {
+    ($($method:ident: $ty:ty),* $(,)?) => {
+        $(
+            fn $method(self, v: $ty) -> Result<IdValue, IdSerializeError> {
+                self.serialize_u64(v as u64)
+            }
+        )*
+    };
+}
+
+impl<P: IdProfile> serde::Serializer for IdValueSerializer<P> {
+    type Ok = IdValue;
+    type Error = IdSerializeError;
+    type SerializeSeq = IdSeqSerializer<P>;
+    type SerializeTuple = IdSeqSerializer<P>;
+    type SerializeTupleStruct = IdSeqSerializer<P>;
+    type SerializeTupleVariant = IdTupleVariantSerializer<P>;
+    type SerializeMap = IdMapSerializer<P>;
+    type SerializeStruct = IdStructSerializer<P>;
+    type SerializeStructVariant = IdStructVariantSerializer<P>;
+
+    fn serialize_bool(self, v: bool) -> Result<IdValue, IdSerializeError> {
+        Ok(IdValue::Bool(v))
+    }
+
+    forward_to_i64!(serialize_i8: i8, serialize_i16: i16, serialize_i32: i32);
+
+    fn serialize_i64(self, v: i64) -> Result<IdValue, IdSerializeError> {
+        Ok(IdValue::Int(v))
+    }
+
+    forward_to_u64!(serialize_u8: u8, serialize_u16: u16, serialize_u32: u32);
+
+    fn serialize_u64(self, v: u64) -> Result<IdValue, IdSerializeError> {
+        Ok(IdValue::UInt(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<IdValue, IdSerializeError> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<IdValue, IdSerializeError> {
+        Ok(IdValue::Float(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<IdValue, IdSerializeError> {
+        Ok(IdValue::Str(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<IdValue, IdSerializeError> {
+        Ok(IdValue::Str(v.to_string()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<IdValue, IdSerializeError> {
+        Ok(IdValue::Array(v.iter().map(|b| IdValue::UInt(*b as u64)).collect()))
+    }
+
+    fn serialize_none(self) -> Result<IdValue, IdSerializeError> {
+        Ok(IdValue::Null)
+    }
+
+    fn serialize_some<T: ?Sized + serde::Serialize>(
+        self,
+        value: &T,
+    ) -> Result<IdValue, IdSerializeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<IdValue, IdSerializeError> {
+        Ok(IdValue::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<IdValue, IdSerializeError> {
+        Ok(IdValue::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<IdValue, IdSerializeError> {
+        if P::disambiguate_unit_variants() {
+            Ok(IdValue::Object(vec![(variant.to_string(), IdValue::Null)]))
+        } else {
+            Ok(IdValue::Str(variant.to_string()))
+        }
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + serde::Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<IdValue, IdSerializeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + serde::Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<IdValue, IdSerializeError> {
+        Ok(IdValue::Object(vec![(
+            variant.to_string(),
+            value.serialize(self)?,
+        )]))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, IdSerializeError> {
+        Ok(IdSeqSerializer {
+            items: Vec::with_capacity(len.unwrap_or(0)),
+            _profile: std::marker::PhantomData,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, IdSerializeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, IdSerializeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, IdSerializeError> {
+        Ok(IdTupleVariantSerializer {
+            variant,
+            items: Vec::with_capacity(len),
+            _profile: std::marker::PhantomData,
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, IdSerializeError> {
+        Ok(IdMapSerializer {
+            entries: Vec::new(),
+            pending_key: None,
+            _profile: std::marker::PhantomData,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, IdSerializeError> {
+        Ok(IdStructSerializer {
+            fields: Vec::with_capacity(len),
+            _profile: std::marker::PhantomData,
+        })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, IdSerializeError> {
+        Ok(IdStructVariantSerializer {
+            variant,
+            fields: Vec::with_capacity(len),
+            _profile: std::marker::PhantomData,
+        })
+    }
+}
+
+struct IdSeqSerializer<P> {
+    items: Vec<IdValue>,
+    _profile: std::marker::PhantomData<P>,
+}
+
+impl<P: IdProfile> serde::ser::SerializeSeq for IdSeqSerializer<P> {
+    type Ok = IdValue;
+    type Error = IdSerializeError;
+
+    fn serialize_element<T: ?Sized + serde::Serialize>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), IdSerializeError> {
+        self.items.push(value.serialize(IdValueSerializer::<P>::new())?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<IdValue, IdSerializeError> {
+        Ok(IdValue::Array(self.items))
+    }
+}
+
+impl<P: IdProfile> serde::ser::SerializeTuple for IdSeqSerializer<P> {
+    type Ok = IdValue;
+    type Error = IdSerializeError;
+
+    fn serialize_element<T: ?Sized + serde::Serialize>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), IdSerializeError> {
+        self.items.push(value.serialize(IdValueSerializer::<P>::new())?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<IdValue, IdSerializeError> {
+        Ok(IdValue::Array(self.items))
+    }
+}
+
+impl<P: IdProfile> serde::ser::SerializeTupleStruct for IdSeqSerializer<P> {
+    type Ok = IdValue;
+    type Error = IdSerializeError;
+
+    fn serialize_field<T: ?Sized + serde::Serialize>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), IdSerializeError> {
+        self.items.push(value.serialize(IdValueSerializer::<P>::new())?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<IdValue, IdSerializeError> {
+        Ok(IdValue::Array(self.items))
+    }
+}
+
+struct IdTupleVariantSerializer<P> {
+    variant: &'static str,
+    items: Vec<IdValue>,
+    _profile: std::marker::PhantomData<P>,
+}
+
+impl<P: IdProfile> serde::ser::SerializeTupleVariant for IdTupleVariantSerializer<P> {
+    type Ok = IdValue;
+    type Error = IdSerializeError;
+
+    fn serialize_field<T: ?Sized + serde::Serialize>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), IdSerializeError> {
+        self.items.push(value.serialize(IdValueSerializer::<P>::new())?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<IdValue, IdSerializeError> {
+        Ok(IdValue::Object(vec![(
+            self.variant.to_string(),
+            IdValue::Array(self.items),
+        )]))
+    }
+}
+
+struct IdMapSerializer<P> {
+    entries: Vec<(String, IdValue)>,
+    pending_key: Option<String>,
+    _profile: std::marker::PhantomData<P>,
+}
+
+/// Map keys are serialized like any other value and then coerced to a
+/// string, since the ID grammar has no separate key type. Only strings and
+/// integers make sense as IDs, so anything else is rejected.
+fn id_value_to_key(value: IdValue) -> Result<String, IdSerializeError> {
+    match value {
+        IdValue::Str(s) => Ok(s),
+        IdValue::Int(i) => Ok(i.to_string()),
+        IdValue::UInt(u) => Ok(u.to_string()),
+        other => Err(IdSerializeError(format!(
+            "map keys must serialize to a string or integer, got {other:?}"
+        ))),
+    }
+}
+
+impl<P: IdProfile> serde::ser::SerializeMap for IdMapSerializer<P> {
+    type Ok = IdValue;
+    type Error = IdSerializeError;
+
+    fn serialize_key<T: ?Sized + serde::Serialize>(
+        &mut self,
+        key: &T,
+    ) -> Result<(), IdSerializeError> {
+        self.pending_key = Some(id_value_to_key(key.serialize(IdValueSerializer::<P>::new())?)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + serde::Serialize>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), IdSerializeError> {
+        let key = self
+            .pending_key
+            .take()
+            .ok_or_else(|| IdSerializeError("serialize_value called before serialize_key".into()))?;
+        self.entries
+            .push((key, value.serialize(IdValueSerializer::<P>::new())?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<IdValue, IdSerializeError> {
+        Ok(IdValue::Map(self.entries))
+    }
+}
+
+struct IdStructSerializer<P> {
+    fields: Vec<(String, IdValue)>,
+    _profile: std::marker::PhantomData<P>,
+}
+
+impl<P: IdProfile> serde::ser::SerializeStruct for IdStructSerializer<P> {
+    type Ok = IdValue;
+    type Error = IdSerializeError;
+
+    fn serialize_field<T: ?Sized + serde::Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), IdSerializeError> {
+        self.fields
+            .push((key.to_string(), value.serialize(IdValueSerializer::<P>::new())?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<IdValue, IdSerializeError> {
+        Ok(IdValue::Object(self.fields))
+    }
+}
+
+struct IdStructVariantSerializer<P> {
+    variant: &'static str,
+    fields: Vec<(String, IdValue)>,
+    _profile: std::marker::PhantomData<P>,
+}
+
+impl<P: IdProfile> serde::ser::SerializeStructVariant for IdStructVariantSerializer<P> {
+    type Ok = IdValue;
+    type Error = IdSerializeError;
+
+    fn serialize_field<T: ?Sized + serde::Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), IdSerializeError> {
+        self.fields
+            .push((key.to_string(), value.serialize(IdValueSerializer::<P>::new())?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<IdValue, IdSerializeError> {
+        Ok(IdValue::Object(vec![(
+            self.variant.to_string(),
+            IdValue::Object(self.fields),
+        )]))
+    }
+}
+
+/// Formats a float the same way no matter where it came from: the shortest
+/// decimal that round-trips back to the same `f64` (Rust's `Display` for
+/// `f64` already guarantees this), `-0.0` normalized to `0`, and a stable
+/// spelling for the non-finite values `serde_json` refuses to serialize at
+/// all.
+fn format_float(f: f64) -> String {
+    if f.is_nan() {
+        return "NaN".to_string();
+    }
+    if f.is_infinite() {
+        return if f > 0.0 { "Inf".to_string() } else { "-Inf".to_string() };
+    }
+    if f == 0.0 {
+        return "0".to_string();
+    }
+    f.to_string()
+}
+
+/// Recursively sorts every *map-derived* object's entries bytewise by key,
+/// so that the same logical value always serializes to the same ID
+/// regardless of the original `HashMap`/`serde_json::Map`'s iteration order.
+///
+/// Struct and struct-variant fields ([`IdValue::Object`]) are left in the
+/// order serde produced them (a struct's declaration order, which is already
+/// deterministic) — only [`IdValue::Map`] is a nondeterminism source, so
+/// only it gets sorted.
+fn canonicalize_value(value: IdValue) -> IdValue {
+    match value {
+        IdValue::Array(items) => IdValue::Array(items.into_iter().map(canonicalize_value).collect()),
+        IdValue::Object(fields) => IdValue::Object(
+            fields
+                .into_iter()
+                .map(|(k, v)| (k, canonicalize_value(v)))
+                .collect(),
+        ),
+        IdValue::Map(fields) => {
+            let mut entries: Vec<(String, IdValue)> = fields
+                .into_iter()
+                .map(|(k, v)| (k, canonicalize_value(v)))
+                .collect();
+            entries.sort_by(|(a, _), (b, _)| a.as_bytes().cmp(b.as_bytes()));
+            IdValue::Map(entries)
+        }
+        other => other,
+    }
+}
+
+/// Recursively escapes every string (both object keys and values) in an
+/// [`IdValue`] under profile `P`, leaving the tree shape and
+/// number/bool/null leaves untouched.
+fn escape_value<P: IdProfile>(value: IdValue) -> IdValue {
+    match value {
+        IdValue::Str(s) => IdValue::Str(escape_reserved_with::<P>(&s)),
+        IdValue::Array(items) => {
+            IdValue::Array(items.into_iter().map(escape_value::<P>).collect())
+        }
+        IdValue::Object(fields) => IdValue::Object(
+            fields
+                .into_iter()
+                .map(|(k, v)| (escape_reserved_with::<P>(&k), escape_value::<P>(v)))
+                .collect(),
+        ),
+        IdValue::Map(fields) => IdValue::Map(
+            fields
+                .into_iter()
+                .map(|(k, v)| (escape_reserved_with::<P>(&k), escape_value::<P>(v)))
+                .collect(),
+        ),
+        other => other,
+    }
+}
+
+/// Renders an already-canonicalized, already-escaped [`IdValue`] into its ID
+/// text. Objects as `{key_value,...}`, arrays as `[value,...]`, numbers/
+/// `null`/`true`/`false` bare, `_` standing in for `:` between a key and its
+/// value, and strings bare *unless* [`is_scalar_lookalike`] would otherwise
+/// make them indistinguishable from one of those bare tokens, in which case
+/// they're wrapped in `"..."` — the one structural byte the grammar never
+/// needs to escape for any other purpose, since a literal quote in content
+/// is always escaped by [`escape_reserved_with`] first.
+fn render_value(value: &IdValue) -> String {
+    match value {
+        IdValue::Null => "null".to_string(),
+        IdValue::Bool(b) => b.to_string(),
+        IdValue::Int(i) => i.to_string(),
+        IdValue::UInt(u) => u.to_string(),
+        IdValue::Float(f) => format_float(*f),
+        IdValue::Str(s) if is_scalar_lookalike(s) => format!("\"{s}\""),
+        IdValue::Str(s) => s.clone(),
+        IdValue::Array(items) => {
+            format!(
+                "[{}]",
+                items.iter().map(render_value).collect::<Vec<_>>().join(",")
+            )
+        }
+        IdValue::Object(fields) | IdValue::Map(fields) => format!("{{{}}}", render_fields(fields)),
+    }
+}
+
+fn render_fields(fields: &[(String, IdValue)]) -> String {
+    fields
+        .iter()
+        .map(|(k, v)| format!("{k}_{}", render_value(v)))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Builds the deterministic ID string for `value` under [`InjectiveProfile`].
+/// Used by `impl_deterministic_display_from_serde!($type)` (no profile
+/// argument); exposed directly for callers that want to encode without
+/// going through `Display`.
+pub fn encode_deterministic_id<T: serde::Serialize>(value: &T) -> Result<String, IdSerializeError> {
+    encode_deterministic_id_with_profile::<T, InjectiveProfile>(value)
+}
+
+/// Builds the deterministic ID string for `value` under profile `P`. Used
+/// by `impl_deterministic_display_from_serde!($type, $profile)`.
+pub fn encode_deterministic_id_with_profile<T: serde::Serialize, P: IdProfile>(
+    value: &T,
+) -> Result<String, IdSerializeError> {
+    let root = escape_value::<P>(canonicalize_value(value.serialize(IdValueSerializer::<P>::new())?));
+    let rendered = match P::variant_tagging() {
+        VariantTagging::Wrapped => render_value(&root),
+        // The top-level wrapper (the outer quotes of a bare string, or the
+        // outer braces of an object) is stripped; everything nested below
+        // it keeps its delimiters.
+        VariantTagging::Suffixed => match &root {
+            IdValue::Object(fields) | IdValue::Map(fields) => render_fields(fields),
+            other => render_value(other),
+        },
+    };
+    Ok(P::transform_case(rendered))
+}
+
+/// Errors produced while parsing a deterministic ID back into its typed
+/// value.
+#[derive(Debug)]
+pub enum IdParseError {
+    /// An `~XX` escape sequence was missing its two hex digits or the digits
+    /// weren't valid hex.
+    MalformedEscape(String),
+    /// The ID doesn't follow the `{...}` / `[...]` / bare-token grammar at
+    /// all (unbalanced delimiters, a key without its value, etc).
+    MalformedId(String),
+    /// The ID parsed as a structurally valid value, but none of the possible
+    /// reconstructions deserialized into the target type.
+    NoMatchingShape(String),
+}
+
+impl std::fmt::Display for IdParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            IdParseError::MalformedEscape(id) => {
+                write!(f, "malformed ~XX escape sequence in id: {id:?}")
+            }
+            IdParseError::MalformedId(id) => write!(f, "malformed id: {id:?}"),
+            IdParseError::NoMatchingShape(id) => {
+                write!(f, "id did not match any known shape: {id:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for IdParseError {}
+
+/// A minimal recursive-descent parser over the ID grammar: tokens are
+/// maximal runs of bytes that don't contain one of the structural
+/// delimiters, and `{`/`}`/`[`/`]`/`,`/`_` (colon) nest exactly like JSON's
+/// `{`/`}`/`[`/`]`/`,`/`:`.
+struct IdParser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> IdParser<'a> {
+    fn new(s: &'a str) -> Self {
+        IdParser {
+            bytes: s.as_bytes(),
+            pos: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn read_token(&mut self) -> &'a str {
+        let start = self.pos;
+        while let Some(b) = self.peek() {
+            if matches!(b, b'{' | b'}' | b'[' | b']' | b',' | b'_') {
+                break;
+            }
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.bytes[start..self.pos])
+            .expect("token boundaries always fall on UTF-8 char boundaries")
+    }
+
+    fn parse_value(&mut self) -> Result<serde_json::Value, IdParseError> {
+        match self.peek() {
+            Some(b'{') => self.parse_object(),
+            Some(b'[') => self.parse_array(),
+            Some(b'"') => self.parse_quoted_string(),
+            _ => scalar_from_token(self.read_token()),
+        }
+    }
+
+    /// Parses a `"..."`-wrapped string, the form [`is_scalar_lookalike`]
+    /// forces on an encode so its content is never mistaken for `null`/
+    /// `true`/`false`/a number. The content between the quotes can't contain
+    /// a literal `"` (it's always escaped by [`escape_reserved_with`]), so
+    /// this just scans to the next quote byte.
+    fn parse_quoted_string(&mut self) -> Result<serde_json::Value, IdParseError> {
+        self.pos += 1; // consume opening '"'
+        let start = self.pos;
+        while self.peek().is_some() && self.peek() != Some(b'"') {
+            self.pos += 1;
+        }
+        let inner = std::str::from_utf8(&self.bytes[start..self.pos])
+            .expect("token boundaries always fall on UTF-8 char boundaries");
+        if self.peek() != Some(b'"') {
+            return Err(IdParseError::MalformedId(self.remainder()));
+        }
+        self.pos += 1; // consume closing '"'
+        Ok(serde_json::Value::String(unescape_reserved(inner)?))
+    }
+
+    fn parse_object(&mut self) -> Result<serde_json::Value, IdParseError> {
+        self.pos += 1; // consume '{'
+        let mut map = serde_json::Map::new();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(serde_json::Value::Object(map));
+        }
+        loop {
+            let key = unescape_reserved(self.read_token())?;
+            if self.peek() != Some(b'_') {
+                return Err(IdParseError::MalformedId(key));
+            }
+            self.pos += 1; // consume colon marker
+            let value = self.parse_value()?;
+            map.insert(key, value);
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    break;
+                }
+                _ => return Err(IdParseError::MalformedId(self.remainder())),
+            }
+        }
+        Ok(serde_json::Value::Object(map))
+    }
+
+    fn parse_array(&mut self) -> Result<serde_json::Value, IdParseError> {
+        self.pos += 1; // consume '['
+        let mut items = Vec::new();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(serde_json::Value::Array(items));
+        }
+        loop {
+            items.push(self.parse_value()?);
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    break;
+                }
+                _ => return Err(IdParseError::MalformedId(self.remainder())),
+            }
+        }
+        Ok(serde_json::Value::Array(items))
+    }
+
+    fn remainder(&self) -> String {
+        String::from_utf8_lossy(&self.bytes[self.pos..]).to_string()
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.pos == self.bytes.len()
+    }
+}
+
+/// Interprets a single raw (unquoted) token as a JSON scalar: `null`/`true`/
+/// `false` keywords, a number (only if re-printing it reproduces the token
+/// exactly, so a value that merely looks like a number is never misread),
+/// or otherwise an escaped string. A token whose content would otherwise be
+/// ambiguous with one of these is instead rendered `"..."` on encode (see
+/// [`is_scalar_lookalike`]) and reaches [`IdParser::parse_quoted_string`],
+/// not this function, so it never falls into the wrong branch here.
+fn scalar_from_token(token: &str) -> Result<serde_json::Value, IdParseError> {
+    match token {
+        "null" => return Ok(serde_json::Value::Null),
+        "true" => return Ok(serde_json::Value::Bool(true)),
+        "false" => return Ok(serde_json::Value::Bool(false)),
+        _ => {}
+    }
+    let as_int = token.parse::<i64>().ok().filter(|i| i.to_string() == token);
+    if let Some(i) = as_int {
+        return Ok(serde_json::Value::Number(i.into()));
+    }
+    // Beyond i64::MAX (e.g. nanosecond timestamps, hashes, counters), still
+    // a valid JSON number, just not representable as i64.
+    let as_uint = token.parse::<u64>().ok().filter(|u| u.to_string() == token);
+    if let Some(u) = as_uint {
+        return Ok(serde_json::Value::Number(u.into()));
+    }
+    let as_float = token
+        .parse::<f64>()
+        .ok()
+        .and_then(serde_json::Number::from_f64)
+        .filter(|n| n.to_string() == token);
+    if let Some(n) = as_float {
+        return Ok(serde_json::Value::Number(n));
+    }
+    Ok(serde_json::Value::String(unescape_reserved(token)?))
+}
+
+/// Whether `s`, left unquoted, would be read back by [`scalar_from_token`]
+/// as `null`/`true`/`false`/a number instead of as this string — i.e.
+/// whether [`render_value`] must wrap it in `"..."` to keep it round-
+/// trippable. Mirrors `scalar_from_token`'s non-string branches exactly.
+fn is_scalar_lookalike(s: &str) -> bool {
+    if matches!(s, "null" | "true" | "false") {
+        return true;
+    }
+    if s.parse::<i64>().ok().is_some_and(|i| i.to_string() == s) {
+        return true;
+    }
+    if s.parse::<u64>().ok().is_some_and(|u| u.to_string() == s) {
+        return true;
+    }
+    s.parse::<f64>()
+        .ok()
+        .and_then(serde_json::Number::from_f64)
+        .is_some_and(|n| n.to_string() == s)
+}
+
+/// Tries to parse `wrapped` as a complete ID value and deserialize it into
+/// `T`. Returns `None` (rather than erroring) on any failure, since the
+/// caller tries several candidate wrappings and only the right one matters.
+fn try_decode<T: serde::de::DeserializeOwned>(wrapped: &str) -> Option<T> {
+    let mut parser = IdParser::new(wrapped);
+    let value = parser.parse_value().ok()?;
+    if !parser.is_exhausted() {
+        return None;
+    }
+    serde_json::from_value(value).ok()
+}
+
+/// Reconstructs a typed value from a deterministic ID. Since encoding
+/// strips the outer braces of a top-level object without recording that it
+/// did, this tries, in order: the object form (re-adding `{`/`}`) and the
+/// bare-token form — returning the first reconstruction that both parses
+/// and deserializes into `T`. A top-level array keeps its `[`/`]` on
+/// encode, so it is read back by the bare-token form.
+///
+/// Note: `NaN`/`Inf`/`-Inf` encode without error (see [`format_float`]), but
+/// since `serde_json::Value` has no way to represent them, decoding a field
+/// that held one of those values will fail rather than round-trip.
+///
+/// A string field whose value happens to look exactly like `null`, `true`,
+/// `false`, or a JSON number (e.g. `"42"`) does *not* collide with the real
+/// thing: [`render_value`] wraps that one token in `"..."` on encode (see
+/// [`is_scalar_lookalike`]), and [`IdParser::parse_quoted_string`] reads it
+/// back as the original string rather than the look-alike scalar.
+pub fn decode_deterministic_id<T: serde::de::DeserializeOwned>(
+    id: &str,
+) -> Result<T, IdParseError> {
+    if let Some(v) = try_decode(&format!("{{{id}}}")) {
+        return Ok(v);
+    }
+    if let Some(v) = try_decode(id) {
+        return Ok(v);
+    }
+    Err(IdParseError::NoMatchingShape(id.to_string()))
+}
+
 #[cfg(test)]
 mod tests {
+    use super::{DeterministicId, IdError};
     use serde::{Deserialize, Serialize};
 
-    #[derive(Serialize, Deserialize, Debug)]
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
     struct ProductCode(String);
 
     impl_deterministic_display_from_serde!(ProductCode);
+    impl_deterministic_parse_from_serde!(ProductCode);
 
-    #[derive(Serialize, Deserialize, Debug)]
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
     #[serde(rename_all = "lowercase")]
     enum SimpleEnum {
         ValueOne,
@@ -69,8 +1159,9 @@ mod tests {
     }
 
     impl_deterministic_display_from_serde!(SimpleEnum);
+    impl_deterministic_parse_from_serde!(SimpleEnum);
 
-    #[derive(Serialize, Deserialize, Debug)]
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
     enum ComplexEnum {
         VariantA,
         VariantB { id: i32, name: String },
@@ -78,6 +1169,7 @@ mod tests {
     }
 
     impl_deterministic_display_from_serde!(ComplexEnum);
+    impl_deterministic_parse_from_serde!(ComplexEnum);
 
     #[test]
     fn test_display_removes_surrounding_quotes_struct() {
@@ -86,9 +1178,9 @@ mod tests {
     }
 
     #[test]
-    fn test_display_preserves_internal_escaped_quotes_struct() {
+    fn test_display_escapes_internal_quotes_struct() {
         let code = ProductCode("ex\"ample".to_string());
-        assert_eq!(code.to_string(), "ex~ample");
+        assert_eq!(code.to_string(), "ex~22ample");
     }
 
     #[test]
@@ -100,7 +1192,7 @@ mod tests {
     #[test]
     fn test_display_simple_enum() {
         assert_eq!(SimpleEnum::ValueOne.to_string(), "valueone");
-        assert_eq!(SimpleEnum::ValueTwo.to_string(), "renamed_value");
+        assert_eq!(SimpleEnum::ValueTwo.to_string(), "renamed~5Fvalue");
         assert_eq!(SimpleEnum::ValueThree.to_string(), "valuethree");
     }
 
@@ -119,9 +1211,303 @@ mod tests {
         assert_eq!(variant_c.to_string(), "VariantC_[one,two]");
     }
 
+    #[test]
+    fn test_display_disambiguates_previously_colliding_values() {
+        // Under the old lossy substitution these two collapsed to the same
+        // string ("a-b"); the injective encoding must keep them distinct.
+        let with_space = ProductCode("a b".to_string());
+        let with_hyphen = ProductCode("a-b".to_string());
+        assert_ne!(with_space.to_string(), with_hyphen.to_string());
+    }
+
     #[test]
     fn test_display_handles_special_characters_struct() {
         let code = ProductCode("text with \n newlines and \t tabs".to_string());
-        assert_eq!(code.to_string(), "text-with-~n-newlines-and-~t-tabs");
+        assert_eq!(
+            code.to_string(),
+            "text~20with~20~0A~20newlines~20and~20~09~20tabs"
+        );
+    }
+
+    #[test]
+    fn test_round_trip_struct() {
+        let code = ProductCode("a value, with: special \"chars\"~and spaces".to_string());
+        let id = code.to_string();
+        assert_eq!(ProductCode::from_id(&id).unwrap(), code);
+    }
+
+    #[test]
+    fn test_round_trip_string_that_looks_like_another_scalar() {
+        for text in ["42", "-17", "3.14", "null", "true", "false"] {
+            let code = ProductCode(text.to_string());
+            let id = code.to_string();
+            assert_eq!(
+                ProductCode::from_id(&id).unwrap(),
+                code,
+                "{text:?} should round-trip as a string, not the scalar it resembles"
+            );
+        }
+    }
+
+    #[test]
+    fn test_display_quotes_string_that_looks_like_a_number() {
+        assert_eq!(ProductCode("42".to_string()).to_string(), "\"42\"");
+        assert_eq!(ProductCode("null".to_string()).to_string(), "\"null\"");
+        // "4.2e1" parses as 42.0 but isn't the canonical spelling of it, so
+        // it never collides with the real number and stays unquoted.
+        assert_eq!(ProductCode("4.2e1".to_string()).to_string(), "4.2e1");
+    }
+
+    #[test]
+    fn test_round_trip_simple_enum() {
+        for variant in [
+            SimpleEnum::ValueOne,
+            SimpleEnum::ValueTwo,
+            SimpleEnum::ValueThree,
+        ] {
+            let id = variant.to_string();
+            assert_eq!(variant, SimpleEnum::from_id(&id).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_round_trip_complex_enum() {
+        let variants = [
+            ComplexEnum::VariantA,
+            ComplexEnum::VariantB {
+                id: 42,
+                name: "te:st, {odd} [name]".to_string(),
+            },
+            ComplexEnum::VariantC(vec!["one".to_string(), "two".to_string()]),
+        ];
+        for variant in variants {
+            let id = variant.to_string();
+            assert_eq!(variant, ComplexEnum::from_id(&id).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_from_str_matches_from_id() {
+        let code = ProductCode("example".to_string());
+        let id = code.to_string();
+        assert_eq!(id.parse::<ProductCode>().unwrap(), code);
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct WithMap {
+        tags: std::collections::HashMap<String, i32>,
+    }
+
+    impl_deterministic_display_from_serde!(WithMap);
+
+    #[test]
+    fn test_display_canonicalizes_map_key_order() {
+        let mut forward = std::collections::HashMap::new();
+        forward.insert("alpha".to_string(), 1);
+        forward.insert("beta".to_string(), 2);
+        forward.insert("gamma".to_string(), 3);
+
+        let mut backward = std::collections::HashMap::new();
+        backward.insert("gamma".to_string(), 3);
+        backward.insert("beta".to_string(), 2);
+        backward.insert("alpha".to_string(), 1);
+
+        let id = WithMap { tags: forward }.to_string();
+        assert_eq!(id, WithMap { tags: backward }.to_string());
+        assert_eq!(id, "tags_{alpha_1,beta_2,gamma_3}");
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct NonAlphabeticalFields {
+        name: String,
+        id: i32,
+    }
+
+    impl_deterministic_display_from_serde!(NonAlphabeticalFields);
+
+    #[test]
+    fn test_display_leaves_struct_field_order_untouched() {
+        // Unlike a map's entries, a struct's fields already have a
+        // deterministic order (declaration order, as serde emits them), so
+        // canonicalization must not re-sort them into `id_7,name_x`.
+        let value = NonAlphabeticalFields {
+            name: "x".to_string(),
+            id: 7,
+        };
+        assert_eq!(value.to_string(), "name_x,id_7");
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Measurement {
+        value: f64,
+    }
+
+    impl_deterministic_display_from_serde!(Measurement);
+    impl_deterministic_parse_from_serde!(Measurement);
+
+    #[test]
+    fn test_display_float_is_shortest_round_trippable_decimal() {
+        assert_eq!(Measurement { value: 0.1 }.to_string(), "value_0.1");
+        assert_eq!(Measurement { value: 3.0 }.to_string(), "value_3");
+        assert_eq!(Measurement { value: -0.0 }.to_string(), "value_0");
+    }
+
+    #[test]
+    fn test_display_float_non_finite_values_do_not_abort() {
+        assert_eq!(
+            Measurement { value: f64::NAN }.to_string(),
+            "value_NaN"
+        );
+        assert_eq!(
+            Measurement { value: f64::INFINITY }.to_string(),
+            "value_Inf"
+        );
+        assert_eq!(
+            Measurement { value: f64::NEG_INFINITY }.to_string(),
+            "value_-Inf"
+        );
+    }
+
+    #[test]
+    fn test_round_trip_float() {
+        let measurement = Measurement { value: 0.1 };
+        let id = measurement.to_string();
+        assert_eq!(Measurement::from_id(&id).unwrap(), measurement);
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct BigU {
+        v: u64,
+    }
+
+    impl_deterministic_display_from_serde!(BigU);
+    impl_deterministic_parse_from_serde!(BigU);
+
+    #[test]
+    fn test_round_trip_u64_beyond_i64_range() {
+        for v in [u64::MAX, 10_000_000_000_000_000_000] {
+            let value = BigU { v };
+            let id = value.to_string();
+            assert_eq!(BigU::from_id(&id).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn test_to_id_matches_display() {
+        let code = ProductCode("example".to_string());
+        assert_eq!(code.to_id().unwrap(), code.to_string());
+    }
+
+    #[derive(Serialize, Debug)]
+    struct WithBoolKeyedMap {
+        flags: std::collections::HashMap<bool, i32>,
+    }
+
+    impl_deterministic_display_from_serde!(WithBoolKeyedMap);
+
+    #[test]
+    fn test_to_id_reports_unsupported_map_key_shape() {
+        let mut flags = std::collections::HashMap::new();
+        flags.insert(true, 1);
+        let value = WithBoolKeyedMap { flags };
+
+        let err = value.to_id().unwrap_err();
+        assert!(matches!(err, IdError::Serialize(_)));
+    }
+
+    use super::{FsSafeProfile, SlugProfile};
+
+    #[derive(Serialize, Debug)]
+    struct SlugCode(String);
+
+    impl_deterministic_display_from_serde!(SlugCode, SlugProfile);
+
+    #[test]
+    fn test_slug_profile_escapes_slashes_and_lowercases() {
+        assert_eq!(
+            SlugCode("Some/Path Segment".to_string()).to_string(),
+            "some-2fpath-20segment"
+        );
+    }
+
+    #[derive(Serialize, Debug)]
+    struct FsSafeCode(String);
+
+    impl_deterministic_display_from_serde!(FsSafeCode, FsSafeProfile);
+
+    #[test]
+    fn test_fs_safe_profile_escapes_path_separators() {
+        assert_eq!(
+            FsSafeCode("a/b*c".to_string()).to_string(),
+            "a$2Fb$2Ac"
+        );
+    }
+
+    #[derive(Serialize, Debug)]
+    enum WrappedEnum {
+        Unit,
+        Payload { id: i32 },
+    }
+
+    struct WrappedTaggingProfile;
+    impl super::IdProfile for WrappedTaggingProfile {
+        fn variant_tagging() -> super::VariantTagging {
+            super::VariantTagging::Wrapped
+        }
+    }
+
+    impl_deterministic_display_from_serde!(WrappedEnum, WrappedTaggingProfile);
+
+    #[test]
+    fn test_wrapped_tagging_profile_keeps_outer_braces() {
+        assert_eq!(WrappedEnum::Unit.to_string(), "Unit");
+        assert_eq!(
+            WrappedEnum::Payload { id: 7 }.to_string(),
+            "{Payload_{id_7}}"
+        );
+    }
+
+    #[derive(Serialize, Debug)]
+    enum Status {
+        Active,
+    }
+
+    impl_deterministic_display_from_serde!(Status);
+
+    #[derive(Serialize, Debug)]
+    struct PlainText(String);
+
+    impl_deterministic_display_from_serde!(PlainText);
+
+    #[test]
+    fn test_default_profile_collides_unit_variant_with_equal_string() {
+        // Documents the default (`InjectiveProfile`) behavior that
+        // disambiguation opts out of: a unit variant and a plain string
+        // field holding the same text render to the same ID.
+        assert_eq!(Status::Active.to_string(), "Active");
+        assert_eq!(PlainText("Active".to_string()).to_string(), "Active");
+    }
+
+    struct DisambiguatedProfile;
+    impl super::IdProfile for DisambiguatedProfile {
+        fn disambiguate_unit_variants() -> bool {
+            true
+        }
+    }
+
+    #[derive(Serialize, Debug)]
+    enum DisambiguatedStatus {
+        Active,
+    }
+
+    impl_deterministic_display_from_serde!(DisambiguatedStatus, DisambiguatedProfile);
+
+    #[test]
+    fn test_disambiguated_profile_separates_unit_variant_from_equal_string() {
+        let unit_id = DisambiguatedStatus::Active.to_string();
+        let text_id = PlainText("Active".to_string()).to_string();
+        assert_ne!(unit_id, text_id);
+        assert_eq!(unit_id, "Active_null");
+        assert_eq!(text_id, "Active");
     }
 }